@@ -1,68 +1,155 @@
 #![allow(soft_unstable)]
 #![feature(test)]
 
-use std::{ fs::File, io::Read, iter::Peekable, marker::PhantomData, ptr };
+use std::{ collections::VecDeque, fs::File, io::Read, marker::PhantomData, ptr };
 
 
 #[derive(Debug)]
 pub enum Token {
 	Identifier(String),
+	Number(i64),
+	Raw(u64)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SliceToken<'a> {
+	Identifier(&'a str),
 	Number(i64)
 }
 
-pub struct Lexer<I: Iterator<Item = u8>> {
-	inner: Peekable<I>
+/// The source location of a token: byte range plus 1-based line/column of its
+/// first character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+	pub start: u32,
+	pub end: u32,
+	pub line: u32,
+	pub col: u32,
+}
+
+/// A token paired with the `Span` it was lexed from.
+#[derive(Debug)]
+pub struct Spanned<T> {
+	pub value: T,
+	pub span: Span,
+}
+
+/// A peekable byte source with position queries, abstracting over the various
+/// readers. Only contiguous in-memory sources report `is_seekable`.
+///
+/// Modeled on classic byte-reader interfaces: a single consuming `read_byte`,
+/// multi-byte lookahead via `peek_byte`/`peek_buf`, and positional queries so
+/// the lexer can record spans and look ahead across token boundaries.
+pub trait ByteIO {
+	/// Consume and return the next byte, or `None` at end of input.
+	fn read_byte (&mut self) -> Option<u8>;
+
+	/// Return the next byte without consuming it, or `None` at end of input.
+	fn peek_byte (&mut self) -> Option<u8>;
+
+	/// Fill `buf` with up to `buf.len()` upcoming bytes without consuming them,
+	/// returning the number of bytes actually available.
+	fn peek_buf (&mut self, buf: &mut [u8]) -> usize;
+
+	/// The current byte offset from the start of the stream.
+	fn tell (&self) -> u64;
+
+	/// Whether the source has been fully consumed.
+	fn is_eof (&mut self) -> bool;
+
+	/// Whether the source supports random access (contiguous in memory).
+	fn is_seekable (&self) -> bool;
+}
+
+pub struct Lexer<B: ByteIO> {
+	inner: B,
+	line: u32,
+	col: u32,
 }
 
-impl<I: Iterator<Item = u8>> Lexer<I> {
-	pub fn new (inner: I) -> Self {
+impl<B: ByteIO> Lexer<B> {
+	pub fn new (inner: B) -> Self {
 		Self {
-			inner: inner.peekable(),
+			inner,
+			line: 1,
+			col: 1,
+		}
+	}
+
+	/// Consume one byte, advancing the line/column counters.
+	fn bump (&mut self) -> Option<u8> {
+		let b = self.inner.read_byte()?;
+
+		if b == b'\n' {
+			self.line += 1;
+			self.col = 1;
+		} else {
+			self.col += 1;
 		}
+
+		Some(b)
 	}
 }
 
-impl<I: Iterator<Item = u8>> Iterator for Lexer<I> {
-	type Item = Token;
+impl<B: ByteIO> Iterator for Lexer<B> {
+	type Item = Spanned<Token>;
 
-	fn next (&mut self) -> Option<Token> {
-		if let Some(&ch) = self.inner.peek() {
+	fn next (&mut self) -> Option<Spanned<Token>> {
+		if let Some(ch) = self.inner.peek_byte() {
 			match ch {
 				x if x.is_ascii_alphabetic() || x == b'_' => {
+					let start = self.inner.tell() as u32;
+					let line = self.line;
+					let col = self.col;
+
 					let mut s = vec![x];
-					self.inner.next();
+					self.bump();
 
-					while let Some(&ch) = self.inner.peek() {
+					while let Some(ch) = self.inner.peek_byte() {
 						if ch.is_ascii_alphanumeric()
 						|| ch == b'_' {
 							s.push(ch);
-							self.inner.next();
+							self.bump();
 						} else {
 							break
 						}
 					}
 
-					Some(Token::Identifier(unsafe { String::from_utf8_unchecked(s) }))
+					let end = self.inner.tell() as u32;
+
+					Some(Spanned {
+						value: Token::Identifier(unsafe { String::from_utf8_unchecked(s) }),
+						span: Span { start, end, line, col },
+					})
 				}
 
 				x if x.is_ascii_digit() => {
+					let start = self.inner.tell() as u32;
+					let line = self.line;
+					let col = self.col;
+
 					let mut s = vec![x];
-					self.inner.next();
+					self.bump();
 
-					while let Some(&ch) = self.inner.peek() {
+					while let Some(ch) = self.inner.peek_byte() {
 						if ch.is_ascii_digit() {
 							s.push(ch);
-							self.inner.next();
+							self.bump();
 						} else {
 							break
 						}
 					}
 
-					Some(Token::Number(unsafe { String::from_utf8_unchecked(s) }.parse().unwrap()))
+					let end = self.inner.tell() as u32;
+
+					Some(Spanned {
+						value: Token::Number(unsafe { String::from_utf8_unchecked(s) }.parse().unwrap()),
+						span: Span { start, end, line, col },
+					})
 				}
 
 				x if x.is_ascii_whitespace() => {
-					self.inner.next();
+					self.bump();
 					self.next()
 				}
 
@@ -77,27 +164,78 @@ impl<I: Iterator<Item = u8>> Iterator for Lexer<I> {
 
 
 pub struct ByteReader<R: Read> {
-	inner: R
+	inner: R,
+	lookahead: VecDeque<u8>,
+	position: u64,
 }
 
 impl<R: Read> ByteReader<R> {
 	pub fn new (inner: R) -> Self {
 		Self {
-			inner
+			inner,
+			lookahead: VecDeque::new(),
+			position: 0,
 		}
 	}
-}
-
-impl<R: Read> Iterator for ByteReader<R> {
-	type Item = u8;
 
-	fn next (&mut self) -> Option<u8> {
+	fn read_raw (&mut self) -> Option<u8> {
 		let mut buf = [0u8; 1];
 		match self.inner.read_exact(&mut buf) {
 			Ok(_) => Some(unsafe { *buf.get_unchecked(0) }),
 			Err(_) => None
 		}
 	}
+
+	fn fill (&mut self, n: usize) {
+		while self.lookahead.len() < n {
+			match self.read_raw() {
+				Some(b) => self.lookahead.push_back(b),
+				None => break
+			}
+		}
+	}
+}
+
+impl<R: Read> ByteIO for ByteReader<R> {
+	fn read_byte (&mut self) -> Option<u8> {
+		let out = match self.lookahead.pop_front() {
+			Some(b) => Some(b),
+			None => self.read_raw()
+		};
+
+		if out.is_some() { self.position += 1; }
+
+		out
+	}
+
+	fn peek_byte (&mut self) -> Option<u8> {
+		self.fill(1);
+		self.lookahead.front().copied()
+	}
+
+	fn peek_buf (&mut self, buf: &mut [u8]) -> usize {
+		self.fill(buf.len());
+
+		let n = buf.len().min(self.lookahead.len());
+		for (slot, &b) in buf.iter_mut().zip(self.lookahead.iter()).take(n) {
+			*slot = b;
+		}
+		n
+	}
+
+	fn tell (&self) -> u64 { self.position }
+
+	fn is_eof (&mut self) -> bool { self.peek_byte().is_none() }
+
+	fn is_seekable (&self) -> bool { false }
+}
+
+impl<R: Read> Iterator for ByteReader<R> {
+	type Item = u8;
+
+	fn next (&mut self) -> Option<u8> {
+		self.read_byte()
+	}
 }
 
 
@@ -114,6 +252,7 @@ pub struct BufferedReader<'b, R: Read> {
 	buffer: &'b mut [u8],
 	offset: usize,
 	remainder: usize,
+	position: u64,
 }
 
 impl<'b, R: Read> BufferedReader<'b, R> {
@@ -123,6 +262,7 @@ impl<'b, R: Read> BufferedReader<'b, R> {
 			buffer,
 			offset: 0,
 			remainder: 0,
+			position: 0,
 		}
 	}
 
@@ -135,10 +275,8 @@ impl<'b, R: Read> BufferedReader<'b, R> {
 	}
 }
 
-impl<'b, R: Read> Iterator for BufferedReader<'b, R> {
-	type Item = u8;
-
-	fn next (&mut self) -> Option<u8> {
+impl<'b, R: Read> ByteIO for BufferedReader<'b, R> {
+	fn read_byte (&mut self) -> Option<u8> {
 		if self.offset == self.remainder {
 			self.refill_buffer()?;
 		}
@@ -146,9 +284,56 @@ impl<'b, R: Read> Iterator for BufferedReader<'b, R> {
 		let offset = self.offset;
 
 		self.offset += 1;
+		self.position += 1;
 
 		Some(unsafe { *self.buffer.get_unchecked(offset) })
 	}
+
+	fn peek_byte (&mut self) -> Option<u8> {
+		if self.offset == self.remainder {
+			self.refill_buffer()?;
+		}
+
+		Some(unsafe { *self.buffer.get_unchecked(self.offset) })
+	}
+
+	fn peek_buf (&mut self, buf: &mut [u8]) -> usize {
+		let n = buf.len();
+
+		// If the requested window straddles the buffer boundary, shift the
+		// unconsumed tail to the front and top the buffer up from the source.
+		if self.remainder - self.offset < n && self.offset != 0 {
+			self.buffer.copy_within(self.offset..self.remainder, 0);
+			self.remainder -= self.offset;
+			self.offset = 0;
+		}
+
+		while self.remainder - self.offset < n && self.remainder < self.buffer.len() {
+			match self.inner.read(&mut self.buffer[self.remainder..]) {
+				Err(_) => break,
+				Ok(0) => break,
+				Ok(k) => self.remainder += k,
+			}
+		}
+
+		let avail = (self.remainder - self.offset).min(n);
+		buf[..avail].copy_from_slice(&self.buffer[self.offset..self.offset + avail]);
+		avail
+	}
+
+	fn tell (&self) -> u64 { self.position }
+
+	fn is_eof (&mut self) -> bool { self.peek_byte().is_none() }
+
+	fn is_seekable (&self) -> bool { false }
+}
+
+impl<'b, R: Read> Iterator for BufferedReader<'b, R> {
+	type Item = u8;
+
+	fn next (&mut self) -> Option<u8> {
+		self.read_byte()
+	}
 }
 
 
@@ -160,16 +345,30 @@ pub struct MMapReader<'f> {
 	ptr: *const u8,
 	end: *const u8,
 
+	#[cfg(windows)]
+	mapping: winapi::shared::ntdef::HANDLE,
+
 	f: PhantomData<&'f mut File>
 }
 
 impl<'f> MMapReader<'f> {
+	#[cfg(unix)]
 	pub fn new (file: &'f mut File) -> Self {
 		use std::os::unix::io::AsRawFd;
 
 		let fd = file.as_raw_fd();
 		let len = file.metadata().unwrap().len() as usize;
 
+		// Mapping a zero-length region is illegal; leave `ptr == end` so the
+		// reader yields no bytes without touching the OS.
+		if len == 0 {
+			return Self {
+				base: ptr::null(), len: 0,
+				ptr: ptr::null(), end: ptr::null(),
+				f: PhantomData
+			};
+		}
+
 		unsafe {
 			let ptr = libc::mmap(
 				ptr::null_mut(),
@@ -189,17 +388,84 @@ impl<'f> MMapReader<'f> {
 			}
 		}
 	}
+
+	#[cfg(windows)]
+	pub fn new (file: &'f mut File) -> Self {
+		use std::os::windows::io::AsRawHandle;
+		use winapi::um::memoryapi::{ CreateFileMappingW, MapViewOfFile, FILE_MAP_READ };
+		use winapi::um::winnt::PAGE_READONLY;
+
+		let len = file.metadata().unwrap().len() as usize;
+
+		// Mapping a zero-length file is illegal on Windows too; leave
+		// `ptr == end` so the reader yields no bytes without touching the OS.
+		if len == 0 {
+			return Self {
+				base: ptr::null(), len: 0,
+				ptr: ptr::null(), end: ptr::null(),
+				mapping: ptr::null_mut(),
+				f: PhantomData
+			};
+		}
+
+		unsafe {
+			let mapping = CreateFileMappingW(
+				file.as_raw_handle() as _,
+				ptr::null_mut(),
+				PAGE_READONLY,
+				0,
+				0,
+				ptr::null()
+			);
+
+			let base = MapViewOfFile(mapping, FILE_MAP_READ, 0, 0, 0) as *const u8;
+
+			let end = base.add(len);
+
+			Self {
+				base, len,
+				ptr: base, end,
+				mapping,
+				f: PhantomData
+			}
+		}
+	}
+
+	pub fn as_slice (&self) -> &[u8] {
+		if self.len == 0 {
+			&[]
+		} else {
+			unsafe { std::slice::from_raw_parts(self.base, self.len) }
+		}
+	}
 }
 
+#[cfg(unix)]
 impl<'f> Drop for MMapReader<'f> {
 	fn drop (&mut self) {
-		unsafe { libc::munmap(self.base as *mut _, self.len); }
+		if !self.base.is_null() {
+			unsafe { libc::munmap(self.base as *mut _, self.len); }
+		}
 	}
 }
 
-impl<'f> Iterator for MMapReader<'f> {
-	type Item = u8;
-	fn next (&mut self) -> Option<u8> {
+#[cfg(windows)]
+impl<'f> Drop for MMapReader<'f> {
+	fn drop (&mut self) {
+		use winapi::um::memoryapi::UnmapViewOfFile;
+		use winapi::um::handleapi::CloseHandle;
+
+		if !self.base.is_null() {
+			unsafe {
+				UnmapViewOfFile(self.base as *mut _);
+				CloseHandle(self.mapping);
+			}
+		}
+	}
+}
+
+impl<'f> ByteIO for MMapReader<'f> {
+	fn read_byte (&mut self) -> Option<u8> {
 		if self.ptr < self.end {
 			unsafe {
 				let out = *self.ptr;
@@ -210,6 +476,373 @@ impl<'f> Iterator for MMapReader<'f> {
 			None
 		}
 	}
+
+	fn peek_byte (&mut self) -> Option<u8> {
+		if self.ptr < self.end {
+			Some(unsafe { *self.ptr })
+		} else {
+			None
+		}
+	}
+
+	fn peek_buf (&mut self, buf: &mut [u8]) -> usize {
+		let avail = buf.len().min(self.end as usize - self.ptr as usize);
+		unsafe { ptr::copy_nonoverlapping(self.ptr, buf.as_mut_ptr(), avail); }
+		avail
+	}
+
+	fn tell (&self) -> u64 {
+		(self.ptr as usize - self.base as usize) as u64
+	}
+
+	fn is_eof (&mut self) -> bool { self.ptr >= self.end }
+
+	fn is_seekable (&self) -> bool { true }
+}
+
+impl<'f> Iterator for MMapReader<'f> {
+	type Item = u8;
+	fn next (&mut self) -> Option<u8> {
+		self.read_byte()
+	}
+}
+
+
+
+/// Byte-class bit flags used by the word-at-a-time scanner. A byte may carry
+/// several flags (a letter is both `IDENT_START` and `IDENT_CONT`, a digit is
+/// both `DIGIT` and `IDENT_CONT`); a byte that carries none has table entry 0.
+const IDENT_START: u8 = 1 << 0;
+const IDENT_CONT:  u8 = 1 << 1;
+const DIGIT:       u8 = 1 << 2;
+const WHITESPACE:  u8 = 1 << 3;
+
+/// 256-entry lookup table classifying every possible byte, built at compile
+/// time so the scanner only does a table load per lane.
+const CLASS: [u8; 256] = build_class_table();
+
+const fn build_class_table () -> [u8; 256] {
+	let mut table = [0u8; 256];
+	let mut i = 0;
+
+	while i < 256 {
+		let b = i as u8;
+		let mut c = 0u8;
+
+		if b.is_ascii_alphabetic() || b == b'_' { c |= IDENT_START | IDENT_CONT; }
+		if b.is_ascii_digit() { c |= DIGIT | IDENT_CONT; }
+		if b.is_ascii_whitespace() { c |= WHITESPACE; }
+
+		table[i] = c;
+		i += 1;
+	}
+
+	table
+}
+
+/// Return the first index at or after `start` whose byte is not in class
+/// `mask`, loading eight bytes at a time and falling back to a scalar loop for
+/// the trailing partial word and the run boundary.
+fn scan_run (data: &[u8], start: usize, mask: u8) -> usize {
+	let mut i = start;
+
+	while i + 8 <= data.len() {
+		let word = u64::from_le_bytes(unsafe { data.get_unchecked(i..i + 8) }.try_into().unwrap());
+
+		let mut lane = 0;
+		while lane < 8 {
+			let byte = (word >> (lane * 8)) as u8;
+			if CLASS[byte as usize] & mask == 0 {
+				return i + lane;
+			}
+			lane += 1;
+		}
+
+		i += 8;
+	}
+
+	while i < data.len() {
+		if CLASS[unsafe { *data.get_unchecked(i) } as usize] & mask == 0 {
+			break
+		}
+		i += 1;
+	}
+
+	i
+}
+
+pub struct SliceLexer<'a> {
+	data: &'a [u8],
+	cursor: usize,
+}
+
+impl<'a> SliceLexer<'a> {
+	pub fn new (data: &'a [u8]) -> Self {
+		Self {
+			data,
+			cursor: 0,
+		}
+	}
+}
+
+impl<'a> Iterator for SliceLexer<'a> {
+	type Item = SliceToken<'a>;
+
+	fn next (&mut self) -> Option<SliceToken<'a>> {
+		if let Some(&ch) = self.data.get(self.cursor) {
+			match ch {
+				x if x.is_ascii_alphabetic() || x == b'_' => {
+					let start = self.cursor;
+					self.cursor += 1;
+
+					while let Some(&ch) = self.data.get(self.cursor) {
+						if ch.is_ascii_alphanumeric()
+						|| ch == b'_' {
+							self.cursor += 1;
+						} else {
+							break
+						}
+					}
+
+					let run = unsafe { self.data.get_unchecked(start..self.cursor) };
+
+					Some(SliceToken::Identifier(unsafe { std::str::from_utf8_unchecked(run) }))
+				}
+
+				x if x.is_ascii_digit() => {
+					let start = self.cursor;
+					self.cursor += 1;
+
+					while let Some(&ch) = self.data.get(self.cursor) {
+						if ch.is_ascii_digit() {
+							self.cursor += 1;
+						} else {
+							break
+						}
+					}
+
+					let run = unsafe { self.data.get_unchecked(start..self.cursor) };
+
+					Some(SliceToken::Number(unsafe { std::str::from_utf8_unchecked(run) }.parse().unwrap()))
+				}
+
+				x if x.is_ascii_whitespace() => {
+					self.cursor += 1;
+					self.next()
+				}
+
+				_ => None
+			}
+		} else {
+			None
+		}
+	}
+}
+
+
+
+/// A `SliceLexer` whose identifier and number runs are found by the
+/// classified word-at-a-time scanner rather than a per-byte loop. It produces
+/// exactly the same token stream as the scalar `SliceLexer`.
+pub struct SimdSliceLexer<'a> {
+	data: &'a [u8],
+	cursor: usize,
+}
+
+impl<'a> SimdSliceLexer<'a> {
+	pub fn new (data: &'a [u8]) -> Self {
+		Self {
+			data,
+			cursor: 0,
+		}
+	}
+}
+
+impl<'a> Iterator for SimdSliceLexer<'a> {
+	type Item = SliceToken<'a>;
+
+	fn next (&mut self) -> Option<SliceToken<'a>> {
+		if let Some(&ch) = self.data.get(self.cursor) {
+			let class = CLASS[ch as usize];
+
+			if class & IDENT_START != 0 {
+				let start = self.cursor;
+				self.cursor = scan_run(self.data, start, IDENT_CONT);
+
+				let run = unsafe { self.data.get_unchecked(start..self.cursor) };
+
+				Some(SliceToken::Identifier(unsafe { std::str::from_utf8_unchecked(run) }))
+			} else if class & DIGIT != 0 {
+				let start = self.cursor;
+				self.cursor = scan_run(self.data, start, DIGIT);
+
+				let run = unsafe { self.data.get_unchecked(start..self.cursor) };
+
+				Some(SliceToken::Number(unsafe { std::str::from_utf8_unchecked(run) }.parse().unwrap()))
+			} else if class & WHITESPACE != 0 {
+				self.cursor = scan_run(self.data, self.cursor, WHITESPACE);
+				self.next()
+			} else {
+				None
+			}
+		} else {
+			None
+		}
+	}
+}
+
+
+
+/// Byte order for the fixed-width integer readers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endian {
+	Little,
+	Big,
+}
+
+/// A lexer that reads fixed-width integers straight from the byte stream rather
+/// than scanning ASCII, for binary formats interleaved with the text grammar.
+///
+/// Each reader fills a small stack buffer with the exact number of bytes, then
+/// reinterprets it through `from_le_bytes`/`from_be_bytes` according to the
+/// configured endianness. A short read at end of input returns `None` cleanly
+/// without emitting a partial value.
+pub struct BinaryLexer<B: ByteIO> {
+	inner: B,
+	endian: Endian,
+}
+
+impl<B: ByteIO> BinaryLexer<B> {
+	pub fn new (inner: B, endian: Endian) -> Self {
+		Self {
+			inner,
+			endian,
+		}
+	}
+
+	/// Fill a fixed `[u8; N]` buffer, or return `None` if the stream ends first.
+	fn read_bytes<const N: usize> (&mut self) -> Option<[u8; N]> {
+		let mut buf = [0u8; N];
+
+		for slot in buf.iter_mut() {
+			*slot = self.inner.read_byte()?;
+		}
+
+		Some(buf)
+	}
+
+	pub fn read_u16 (&mut self) -> Option<u16> {
+		let buf = self.read_bytes::<2>()?;
+		Some(match self.endian {
+			Endian::Little => u16::from_le_bytes(buf),
+			Endian::Big => u16::from_be_bytes(buf),
+		})
+	}
+
+	pub fn read_u32 (&mut self) -> Option<u32> {
+		let buf = self.read_bytes::<4>()?;
+		Some(match self.endian {
+			Endian::Little => u32::from_le_bytes(buf),
+			Endian::Big => u32::from_be_bytes(buf),
+		})
+	}
+
+	pub fn read_u64 (&mut self) -> Option<u64> {
+		let buf = self.read_bytes::<8>()?;
+		Some(match self.endian {
+			Endian::Little => u64::from_le_bytes(buf),
+			Endian::Big => u64::from_be_bytes(buf),
+		})
+	}
+
+	pub fn read_i16 (&mut self) -> Option<i16> {
+		let buf = self.read_bytes::<2>()?;
+		Some(match self.endian {
+			Endian::Little => i16::from_le_bytes(buf),
+			Endian::Big => i16::from_be_bytes(buf),
+		})
+	}
+
+	pub fn read_i32 (&mut self) -> Option<i32> {
+		let buf = self.read_bytes::<4>()?;
+		Some(match self.endian {
+			Endian::Little => i32::from_le_bytes(buf),
+			Endian::Big => i32::from_be_bytes(buf),
+		})
+	}
+
+	pub fn read_i64 (&mut self) -> Option<i64> {
+		let buf = self.read_bytes::<8>()?;
+		Some(match self.endian {
+			Endian::Little => i64::from_le_bytes(buf),
+			Endian::Big => i64::from_be_bytes(buf),
+		})
+	}
+
+	/// Read a `u64` and surface it as a `Token::Raw`.
+	pub fn read_raw (&mut self) -> Option<Token> {
+		self.read_u64().map(Token::Raw)
+	}
+
+	/// Read an `i64` and surface it as a `Token::Number`.
+	pub fn read_number (&mut self) -> Option<Token> {
+		self.read_i64().map(Token::Number)
+	}
+}
+
+
+
+/// The zstd frame magic, matched against the first four bytes of the stream.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+
+/// A `Read` adapter that sniffs the leading magic of its source and, if it
+/// recognizes a compressed frame, transparently inflates it; otherwise the
+/// sniffed bytes are replayed and the raw stream passes through untouched.
+///
+/// Unlike `MMapReader`/`SliceLexer`, decompression yields bytes from a
+/// streaming decoder rather than a contiguous region, so it is consumed through
+/// `ByteReader` like any other `Read` source.
+pub struct DecompressingReader {
+	inner: Box<dyn Read>,
+}
+
+impl DecompressingReader {
+	pub fn new<R: Read + 'static> (mut inner: R) -> Self {
+		let mut magic = [0u8; 4];
+		let sniffed = sniff(&mut inner, &mut magic);
+
+		// Put the sniffed prefix back in front of the still-unread tail.
+		let replay = std::io::Cursor::new(magic[..sniffed].to_vec()).chain(inner);
+
+		let boxed: Box<dyn Read> = if sniffed == ZSTD_MAGIC.len() && magic == ZSTD_MAGIC {
+			Box::new(zstd::stream::read::Decoder::new(replay).unwrap())
+		} else {
+			Box::new(replay)
+		};
+
+		Self { inner: boxed }
+	}
+}
+
+impl Read for DecompressingReader {
+	fn read (&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+		self.inner.read(buf)
+	}
+}
+
+/// Read up to `buf.len()` bytes, coping with short reads, and return the count.
+fn sniff<R: Read> (inner: &mut R, buf: &mut [u8]) -> usize {
+	let mut filled = 0;
+
+	while filled < buf.len() {
+		match inner.read(&mut buf[filled..]) {
+			Err(_) => break,
+			Ok(0) => break,
+			Ok(n) => filled += n,
+		}
+	}
+
+	filled
 }
 
 
@@ -227,7 +860,7 @@ mod tests {
 	const MB: usize = KB * 1024;
 
 	#[inline(always)]
-	fn finalize<I: Iterator<Item = Token>> (it: I) {
+	fn finalize<T, I: Iterator<Item = T>> (it: I) {
 		let v: Vec<_> = it.collect();
 
 		assert_eq!(v.len(), EXPECTED_ITEMS);
@@ -270,7 +903,7 @@ mod tests {
 		let mut f = File::open(TEST_FILE).unwrap();
 		let mut s = String::new();
 		f.read_to_string(&mut s).unwrap();
-		let a = s.into_bytes().into_iter();
+		let a = ByteReader::new(s.as_bytes());
 		let b = Lexer::new(a);
 
 		finalize(b)
@@ -281,7 +914,7 @@ mod tests {
 		let mut f = File::open(TEST_FILE).unwrap();
 		let mut v = Vec::new();
 		f.read_to_end(&mut v).unwrap();
-		let a = v.into_iter();
+		let a = ByteReader::new(v.as_slice());
 		let b = Lexer::new(a);
 
 		finalize(b)
@@ -296,6 +929,158 @@ mod tests {
 		finalize(b)
 	}
 
+	#[test]
+	fn test_slice_vec () {
+		let mut f = File::open(TEST_FILE).unwrap();
+		let mut v = Vec::new();
+		f.read_to_end(&mut v).unwrap();
+		let b = SliceLexer::new(&v);
+
+		finalize(b)
+	}
+
+	#[test]
+	fn test_slice_mmap () {
+		let mut f = File::open(TEST_FILE).unwrap();
+		let a = MMapReader::new(&mut f);
+		let b = SliceLexer::new(a.as_slice());
+
+		finalize(b)
+	}
+
+	#[test]
+	fn test_slice_mmap_simd () {
+		let mut f = File::open(TEST_FILE).unwrap();
+		let a = MMapReader::new(&mut f);
+		let b = SimdSliceLexer::new(a.as_slice());
+
+		finalize(b)
+	}
+
+	#[test]
+	fn test_slice_simd_matches_scalar () {
+		let mut f = File::open(TEST_FILE).unwrap();
+		let mut v = Vec::new();
+		f.read_to_end(&mut v).unwrap();
+
+		let scalar: Vec<_> = SliceLexer::new(&v).collect();
+		let simd: Vec<_> = SimdSliceLexer::new(&v).collect();
+
+		assert_eq!(scalar, simd);
+	}
+
+	fn compressed_test_file () -> Vec<u8> {
+		let mut v = Vec::new();
+		File::open(TEST_FILE).unwrap().read_to_end(&mut v).unwrap();
+		zstd::encode_all(&v[..], 0).unwrap()
+	}
+
+	#[test]
+	fn test_decompress_plain () {
+		let f = File::open(TEST_FILE).unwrap();
+		let a = ByteReader::new(DecompressingReader::new(f));
+		let b = Lexer::new(a);
+
+		finalize(b)
+	}
+
+	#[test]
+	fn test_decompress_zstd () {
+		let data = compressed_test_file();
+		let a = ByteReader::new(DecompressingReader::new(std::io::Cursor::new(data)));
+		let b = Lexer::new(a);
+
+		finalize(b)
+	}
+
+	#[test]
+	fn test_binary_endianness () {
+		let le = [0x78, 0x56, 0x34, 0x12];
+		let mut bl = BinaryLexer::new(ByteReader::new(&le[..]), Endian::Little);
+		assert_eq!(bl.read_u32(), Some(0x12345678));
+
+		let be = [0x12, 0x34, 0x56, 0x78];
+		let mut bl = BinaryLexer::new(ByteReader::new(&be[..]), Endian::Big);
+		assert_eq!(bl.read_u32(), Some(0x12345678));
+	}
+
+	#[test]
+	fn test_binary_roundtrip () {
+		let value: u64 = 0x0123456789ABCDEF;
+
+		for endian in [Endian::Little, Endian::Big] {
+			let bytes = match endian {
+				Endian::Little => value.to_le_bytes(),
+				Endian::Big => value.to_be_bytes(),
+			};
+
+			let mut bl = BinaryLexer::new(ByteReader::new(&bytes[..]), endian);
+			assert_eq!(bl.read_u64(), Some(value));
+		}
+
+		// Signed values survive the round-trip too, across a BufferedReader.
+		let signed: i32 = -12345;
+		let bytes = signed.to_le_bytes();
+		let mut buffer = make_box(2);
+		let mut bl = BinaryLexer::new(BufferedReader::new(&bytes[..], &mut buffer), Endian::Little);
+		assert_eq!(bl.read_i32(), Some(signed));
+	}
+
+	#[test]
+	fn test_binary_tokens () {
+		let bytes = 42u64.to_be_bytes();
+		let mut bl = BinaryLexer::new(ByteReader::new(&bytes[..]), Endian::Big);
+		match bl.read_raw() {
+			Some(Token::Raw(v)) => assert_eq!(v, 42),
+			other => panic!("{:?}", other),
+		}
+	}
+
+	#[test]
+	fn test_binary_short_read () {
+		// Three bytes cannot satisfy a u32; the read fails cleanly.
+		let data = [0x01, 0x02, 0x03];
+		let mut bl = BinaryLexer::new(ByteReader::new(&data[..]), Endian::Little);
+		assert_eq!(bl.read_u32(), None);
+	}
+
+	#[test]
+	fn test_spans () {
+		let src = b"  foo 123\nbar";
+		let mut lx = Lexer::new(ByteReader::new(&src[..]));
+
+		let a = lx.next().unwrap();
+		match a.value { Token::Identifier(ref s) => assert_eq!(s, "foo"), _ => panic!() }
+		assert_eq!(a.span, Span { start: 2, end: 5, line: 1, col: 3 });
+
+		let b = lx.next().unwrap();
+		match b.value { Token::Number(n) => assert_eq!(n, 123), _ => panic!() }
+		assert_eq!(b.span, Span { start: 6, end: 9, line: 1, col: 7 });
+
+		let c = lx.next().unwrap();
+		match c.value { Token::Identifier(ref s) => assert_eq!(s, "bar"), _ => panic!() }
+		assert_eq!(c.span, Span { start: 10, end: 13, line: 2, col: 1 });
+
+		assert!(lx.next().is_none());
+	}
+
+	#[test]
+	fn test_spans_across_buffer_boundary () {
+		// A tiny buffer forces refills in the middle of runs; spans must be
+		// identical to the fully-buffered case.
+		let src = b"  foo 123\nbar";
+		let mut buffer = make_box(4);
+		let mut lx = Lexer::new(BufferedReader::new(&src[..], &mut buffer));
+
+		let spans: Vec<_> = (&mut lx).map(|t| t.span).collect();
+
+		assert_eq!(spans, vec![
+			Span { start: 2, end: 5, line: 1, col: 3 },
+			Span { start: 6, end: 9, line: 1, col: 7 },
+			Span { start: 10, end: 13, line: 2, col: 1 },
+		]);
+	}
+
 
 
 	#[bench]
@@ -329,4 +1114,30 @@ mod tests {
 	fn bench_mmap (b: &mut test::Bencher) {
 		b.iter(test_mmap)
 	}
+
+	#[bench]
+	fn bench_slice_vec (b: &mut test::Bencher) {
+		b.iter(test_slice_vec)
+	}
+
+	#[bench]
+	fn bench_slice_mmap (b: &mut test::Bencher) {
+		b.iter(test_slice_mmap)
+	}
+
+	#[bench]
+	fn bench_slice_mmap_simd (b: &mut test::Bencher) {
+		b.iter(test_slice_mmap_simd)
+	}
+
+	#[bench]
+	fn bench_decompress (b: &mut test::Bencher) {
+		let data = compressed_test_file();
+
+		b.iter(|| {
+			let a = ByteReader::new(DecompressingReader::new(std::io::Cursor::new(data.clone())));
+			let lx = Lexer::new(a);
+			finalize(lx)
+		})
+	}
 }